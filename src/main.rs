@@ -1,30 +1,99 @@
+use glob::Pattern;
+use ignore::WalkBuilder;
 use palc::Parser;
 use rayon::prelude::*;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
-use walkdir::WalkDir;
+
+/// Disambiguates temp file names when multiple threads convert files in the same directory.
+static TMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Default cutoff above which files are converted via the streaming path instead
+/// of being loaded fully into memory.
+const DEFAULT_MAX_INMEM: u64 = 64 * 1024 * 1024;
+
+/// Chunk size used when streaming a large file through the conversion.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
 
 /// A high-performance tool for recursively converting line endings of all text files in a folder to LF.
 #[derive(Parser, Debug)]
 struct Args {
     /// The path to the folder to process
     path: Option<PathBuf>,
+
+    /// Only report files that would be converted, without writing anything.
+    /// Exits with a non-zero status if any such file is found.
+    #[arg(long)]
+    check: bool,
+
+    /// With --check, only print the offending paths, one per line.
+    #[arg(long)]
+    quiet: bool,
+
+    /// Only process paths matching this glob (e.g. `*.md`). May be repeated.
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Skip paths matching this glob (e.g. `target/**`). May be repeated.
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// Files larger than this many bytes are converted via a streaming pass
+    /// instead of being loaded fully into memory.
+    #[arg(long, default_value_t = DEFAULT_MAX_INMEM)]
+    max_inmem: u64,
+}
+
+/// Returns whether `path` should be processed given the `--include`/`--exclude`
+/// glob filters: excluded if it matches any exclude pattern, and (when include
+/// patterns are given) only kept if it also matches at least one of them.
+fn path_is_allowed(path: &Path, includes: &[Pattern], excludes: &[Pattern]) -> bool {
+    // Strip a leading "./" (present whenever the walk root is the default ".")
+    // so patterns like `target/**` match without the caller needing to know
+    // how the root was spelled.
+    let path = path.strip_prefix(".").unwrap_or(path);
+
+    if excludes.iter().any(|p| p.matches_path(path)) {
+        return false;
+    }
+    includes.is_empty() || includes.iter().any(|p| p.matches_path(path))
+}
+
+/// Parses `--include`/`--exclude` glob patterns, printing a clean error and
+/// exiting non-zero (rather than panicking) if one is malformed.
+fn parse_glob_patterns(flag: &str, raw: &[String]) -> Vec<Pattern> {
+    raw.iter()
+        .map(|p| {
+            Pattern::new(p).unwrap_or_else(|e| {
+                eprintln!("Error: invalid {} glob {:?}: {}", flag, p, e);
+                std::process::exit(1);
+            })
+        })
+        .collect()
 }
 
 fn main() {
     let args = Args::parse();
-    let root_path = args.path.unwrap_or_else(|| ".".into());
+    let root_path = args.path.clone().unwrap_or_else(|| ".".into());
+
+    let includes = parse_glob_patterns("--include", &args.include);
+    let excludes = parse_glob_patterns("--exclude", &args.exclude);
 
     if root_path.is_dir() {
         let start_time = Instant::now();
         let processed_count = AtomicUsize::new(0);
         let skipped_count = AtomicUsize::new(0);
         let error_count = AtomicUsize::new(0);
-        WalkDir::new(&root_path)
-            .into_iter()
+        // `WalkBuilder` honors .gitignore, .ignore, and global git excludes by default.
+        // Hidden files are kept (unlike the `ignore` crate's default) since a line-ending
+        // fixer should still convert things like `.github/workflows/*.yml`; `.git` itself
+        // is excluded explicitly below instead.
+        WalkBuilder::new(&root_path)
+            .hidden(false)
+            .build()
             .par_bridge()
             .for_each(|entry_result| {
                 let entry = match entry_result {
@@ -37,11 +106,28 @@ fn main() {
                 };
 
                 let path = entry.path();
-                if path.is_file() {
-                    match process_file(path) {
+                let in_git_dir = path.components().any(|c| c.as_os_str() == ".git");
+                if path.is_file() && !in_git_dir && path_is_allowed(path, &includes, &excludes) {
+                    let result = if args.check {
+                        check_file(path).inspect(|&needs_conversion| {
+                            if needs_conversion {
+                                if args.quiet {
+                                    println!("{}", path.display());
+                                } else {
+                                    println!("Would convert: {}", path.display());
+                                }
+                            }
+                        })
+                    } else {
+                        process_file(path, args.max_inmem)
+                    };
+
+                    match result {
                         Ok(true) => {
                             processed_count.fetch_add(1, Ordering::SeqCst);
-                            println!("Processed: {}", path.display());
+                            if !args.check && !args.quiet {
+                                println!("Processed: {}", path.display());
+                            }
                         }
                         Ok(false) => {
                             skipped_count.fetch_add(1, Ordering::SeqCst);
@@ -55,66 +141,417 @@ fn main() {
             });
 
         let duration = start_time.elapsed();
-        println!("\n--- Processing Complete ---");
-        println!(
-            "Files successfully converted: {}",
-            processed_count.load(Ordering::SeqCst)
-        );
-        println!("Files skipped: {}", skipped_count.load(Ordering::SeqCst));
-        println!("Errors encountered: {}", error_count.load(Ordering::SeqCst));
-        println!("Total time: {:?}", duration);
+        if !args.quiet {
+            println!("\n--- Processing Complete ---");
+            println!(
+                "Files {}: {}",
+                if args.check { "that would be converted" } else { "successfully converted" },
+                processed_count.load(Ordering::SeqCst)
+            );
+            println!("Files skipped: {}", skipped_count.load(Ordering::SeqCst));
+            println!("Errors encountered: {}", error_count.load(Ordering::SeqCst));
+            println!("Total time: {:?}", duration);
+        }
+
+        if args.check && (processed_count.load(Ordering::SeqCst) > 0 || error_count.load(Ordering::SeqCst) > 0) {
+            std::process::exit(1);
+        }
     } else if root_path.is_file() {
         let path = root_path.as_path();
-        match process_file(path) {
-            Ok(true) => {
-                println!("Processed: {}", path.display());
-            }
-            Ok(false) => {
-                eprintln!("Skipped: {}", path.display());
+        if args.check {
+            match check_file(path) {
+                Ok(true) => {
+                    if args.quiet {
+                        println!("{}", path.display());
+                    } else {
+                        println!("Would convert: {}", path.display());
+                    }
+                    std::process::exit(1);
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    eprintln!("Error processing file {}: {}", path.display(), e);
+                    std::process::exit(1);
+                }
             }
-            Err(e) => {
-                eprintln!("Error processing file {}: {}", path.display(), e);
+        } else {
+            match process_file(path, args.max_inmem) {
+                Ok(true) => {
+                    println!("Processed: {}", path.display());
+                }
+                Ok(false) => {
+                    eprintln!("Skipped: {}", path.display());
+                }
+                Err(e) => {
+                    eprintln!("Error processing file {}: {}", path.display(), e);
+                }
             }
         }
     }
 }
 
+/// Determines whether a file contains CRLF line endings that would be converted,
+/// without writing anything back to disk.
+///
+/// Returns:
+/// - `Ok(true)` if the file is text and contains at least one CRLF.
+/// - `Ok(false)` if the file was skipped (not a text file or already LF).
+/// - `Err(io::Error)` if an I/O error occurs.
+fn check_file(path: &Path) -> io::Result<bool> {
+    if !is_likely_text_file(path)? {
+        return Ok(false);
+    }
+
+    let data = fs::read(path)?;
+    Ok(contains_crlf(&data))
+}
+
 /// Processes a single file, converting line endings.
 ///
+/// Files larger than `max_inmem` bytes are converted through a streaming pass
+/// that never holds the whole file in memory; smaller files take the faster
+/// in-memory path.
+///
 /// Returns:
 /// - `Ok(true)` if the file was successfully converted.
 /// - `Ok(false)` if the file was skipped (not a text file or already LF).
 /// - `Err(io::Error)` if an I/O error occurs.
-fn process_file(path: &Path) -> io::Result<bool> {
+fn process_file(path: &Path, max_inmem: u64) -> io::Result<bool> {
     // 1. Check if it's likely a text file
     if !is_likely_text_file(path)? {
         return Ok(false);
     }
 
-    // 2. Read file content
-    let content = fs::read_to_string(path)?;
+    let metadata = fs::metadata(path)?;
+    if metadata.len() > max_inmem {
+        return process_file_streaming(path);
+    }
+
+    // 2. Read file content as raw bytes, so non-UTF-8 and UTF-16 text are handled too.
+    // Pre-size the buffer from the file length, as `fs::read` does for its fast path.
+    let mut data = Vec::with_capacity(metadata.len() as usize);
+    fs::File::open(path)?.read_to_end(&mut data)?;
 
     // 3. Check if it contains CRLF
-    if !content.contains("\r\n") {
+    if !contains_crlf(&data) {
         return Ok(false);
     }
 
     // 4. Replace CRLF with LF
-    let new_content = content.replace("\r\n", "\n");
+    let new_data = convert_crlf(&data);
+
+    // 5. Write back to file atomically, so a crash mid-write can't corrupt the source
+    write_atomic(path, &new_data)?;
+
+    Ok(true)
+}
+
+/// Converts a large file without loading it fully into memory: a first pass
+/// streams through in fixed-size chunks to check for CRLF (so untouched files
+/// are never written to), and a second pass streams the conversion straight
+/// into the atomic temp file. A UTF-16 BOM needs multi-byte lookahead that the
+/// single-`\r`-carry streaming below doesn't support, so those files still take
+/// the in-memory path regardless of size.
+fn process_file_streaming(path: &Path) -> io::Result<bool> {
+    {
+        let mut probe = [0u8; 2];
+        let n = fs::File::open(path)?.read(&mut probe)?;
+        if detect_utf16_bom(&probe[..n]).is_some() {
+            let data = fs::read(path)?;
+            if !contains_crlf(&data) {
+                return Ok(false);
+            }
+            write_atomic(path, &convert_crlf(&data))?;
+            return Ok(true);
+        }
+    }
 
-    // 5. Write back to file
-    fs::write(path, new_content)?;
+    if !streaming_contains_crlf(path)? {
+        return Ok(false);
+    }
 
+    streaming_convert_crlf(path)?;
     Ok(true)
 }
 
+/// First streaming pass: scans `path` in fixed-size chunks for a CRLF sequence,
+/// carrying a single trailing `\r` across the chunk boundary so a split CRLF is
+/// still detected.
+fn streaming_contains_crlf(path: &Path) -> io::Result<bool> {
+    let mut reader = fs::File::open(path)?;
+    let mut buf = [0u8; STREAM_CHUNK_SIZE];
+    let mut pending_cr = false;
+
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        if pending_cr && buf[0] == b'\n' {
+            return Ok(true);
+        }
+        if buf[..n].windows(2).any(|w| w == [b'\r', b'\n']) {
+            return Ok(true);
+        }
+        pending_cr = buf[n - 1] == b'\r';
+    }
+
+    Ok(false)
+}
+
+/// Second streaming pass: rewrites `path` with every CRLF collapsed to LF,
+/// writing through the same atomic temp-file-then-rename path as the in-memory
+/// conversion, again carrying a single trailing `\r` across chunk boundaries.
+fn streaming_convert_crlf(path: &Path) -> io::Result<()> {
+    write_atomic_with(path, |out| {
+        let mut reader = fs::File::open(path)?;
+        let mut buf = [0u8; STREAM_CHUNK_SIZE];
+        let mut pending_cr = false;
+
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+
+            let mut chunk = Vec::with_capacity(n);
+            let mut i = 0;
+            if pending_cr {
+                if buf[0] == b'\n' {
+                    chunk.push(b'\n');
+                    i = 1;
+                } else {
+                    chunk.push(b'\r');
+                }
+                pending_cr = false;
+            }
+            while i < n {
+                if buf[i] == b'\r' {
+                    if i + 1 < n {
+                        if buf[i + 1] == b'\n' {
+                            chunk.push(b'\n');
+                            i += 2;
+                            continue;
+                        }
+                    } else {
+                        pending_cr = true;
+                        i += 1;
+                        continue;
+                    }
+                }
+                chunk.push(buf[i]);
+                i += 1;
+            }
+            out.write_all(&chunk)?;
+        }
+
+        if pending_cr {
+            out.write_all(b"\r")?;
+        }
+        Ok(())
+    })
+}
+
+/// The UTF-16 byte order a BOM indicates, and therefore the width the CRLF scan
+/// must step by so it doesn't match the zero byte halves of UTF-16 code units.
+enum Utf16Endian {
+    Little,
+    Big,
+}
+
+/// Detects a leading UTF-16 byte-order mark, if any.
+fn detect_utf16_bom(data: &[u8]) -> Option<Utf16Endian> {
+    if data.starts_with(&[0xFF, 0xFE]) {
+        Some(Utf16Endian::Little)
+    } else if data.starts_with(&[0xFE, 0xFF]) {
+        Some(Utf16Endian::Big)
+    } else {
+        None
+    }
+}
+
+/// Returns whether `data` contains a CRLF sequence, matching the two-byte `\r\n`
+/// for ASCII-superset single-byte encodings or the encoding-appropriate 16-bit
+/// CRLF when a UTF-16 BOM is present.
+fn contains_crlf(data: &[u8]) -> bool {
+    match detect_utf16_bom(data) {
+        Some(Utf16Endian::Little) => data.windows(4).any(|w| w == [0x0D, 0x00, 0x0A, 0x00]),
+        Some(Utf16Endian::Big) => data.windows(4).any(|w| w == [0x00, 0x0D, 0x00, 0x0A]),
+        None => data.windows(2).any(|w| w == [0x0D, 0x0A]),
+    }
+}
+
+/// Rewrites every CRLF sequence in `data` to LF, at the byte width appropriate
+/// for the encoding detected via `detect_utf16_bom`.
+fn convert_crlf(data: &[u8]) -> Vec<u8> {
+    match detect_utf16_bom(data) {
+        Some(Utf16Endian::Little) => strip_crlf(data, &[0x0D, 0x00, 0x0A, 0x00], &[0x0A, 0x00]),
+        Some(Utf16Endian::Big) => strip_crlf(data, &[0x00, 0x0D, 0x00, 0x0A], &[0x00, 0x0A]),
+        None => strip_crlf(data, &[0x0D, 0x0A], &[0x0A]),
+    }
+}
+
+/// Copies `data` into a new buffer with every occurrence of `from` replaced by `to`.
+fn strip_crlf(data: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i..].starts_with(from) {
+            out.extend_from_slice(to);
+            i += from.len();
+        } else {
+            out.push(data[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Writes `contents` to `path` atomically via [`write_atomic_with`].
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    write_atomic_with(path, |tmp_file| tmp_file.write_all(contents))
+}
+
+/// Atomically replaces `path` with the bytes `write_fn` writes to the temp file
+/// it's given: the data lands in a temporary file in the same directory (so the
+/// final rename stays on one filesystem), the original file's permission bits
+/// are copied over, and the temp file is renamed onto `path` only after a
+/// successful flush. On any error the temp file is removed and `path` is left
+/// untouched.
+fn write_atomic_with(path: &Path, write_fn: impl FnOnce(&mut fs::File) -> io::Result<()>) -> io::Result<()> {
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?
+        .to_string_lossy();
+    let unique = TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let tmp_path = dir.join(format!(".{}.lf-tmp-{}-{}", file_name, std::process::id(), unique));
+
+    let result = (|| -> io::Result<()> {
+        let mut tmp_file = fs::File::create(&tmp_path)?;
+        write_fn(&mut tmp_file)?;
+        tmp_file.flush()?;
+        tmp_file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&tmp_path, metadata.permissions())?;
+        }
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
 /// Determines if a file is likely a text file by reading the first 1024 bytes and checking for NULL bytes.
-/// This is a heuristic method, effective for most cases.
+/// This is a heuristic method, effective for most cases. A leading UTF-16 BOM is
+/// special-cased as text, since UTF-16 interleaves NUL bytes that would otherwise
+/// be misclassified as binary.
 fn is_likely_text_file(path: &Path) -> io::Result<bool> {
     let mut file = fs::File::open(path)?;
     let mut buffer = [0; 1024];
     let n = file.read(&mut buffer)?;
+    let buffer = &buffer[..n];
+
+    if detect_utf16_bom(buffer).is_some() {
+        return Ok(true);
+    }
 
     // Check if the buffer contains NULL bytes (0x00)
-    Ok(!buffer[..n].contains(&0))
+    Ok(!buffer.contains(&0))
+}
+
+/// Golden-file regression tests: each `testdata/*.in` fixture is run through the
+/// same text-detection and CRLF-conversion logic as `process_file` and compared
+/// against its `*.out` snapshot. Set `BLESS=1` to rewrite the snapshots in place
+/// instead of asserting, then review the diff.
+#[cfg(test)]
+mod golden_tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Mirrors `process_file`'s decision logic against in-memory bytes, so the
+    /// fixtures can be exercised without touching the filesystem.
+    fn convert_like_process_file(data: &[u8]) -> Vec<u8> {
+        let probe = &data[..data.len().min(1024)];
+        let is_text = detect_utf16_bom(probe).is_some() || !probe.contains(&0);
+        if !is_text || !contains_crlf(data) {
+            return data.to_vec();
+        }
+        convert_crlf(data)
+    }
+
+    fn testdata_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata")
+    }
+
+    #[test]
+    fn golden_files_match_snapshots() {
+        let dir = testdata_dir();
+        let bless = std::env::var_os("BLESS").is_some();
+
+        let mut inputs: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap_or_else(|e| panic!("failed to read {}: {}", dir.display(), e))
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|e| e.to_str()) == Some("in"))
+            .collect();
+        inputs.sort();
+
+        assert!(!inputs.is_empty(), "no fixtures found in {}", dir.display());
+
+        let failures: Vec<String> = inputs
+            .par_iter()
+            .filter_map(|input_path| {
+                let expected_path = input_path.with_extension("out");
+                let input = fs::read(input_path).expect("read fixture input");
+                let actual = convert_like_process_file(&input);
+
+                if bless {
+                    fs::write(&expected_path, &actual).expect("write blessed snapshot");
+                    return None;
+                }
+
+                let expected = fs::read(&expected_path)
+                    .unwrap_or_else(|e| panic!("missing snapshot {}: {}", expected_path.display(), e));
+                if actual != expected {
+                    Some(format!("{} did not match its snapshot", input_path.display()))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    /// Regression test for the streaming path: builds a file whose CRLF falls
+    /// exactly on a `STREAM_CHUNK_SIZE` boundary, runs it through
+    /// `process_file_streaming`, and checks the result against the same
+    /// in-memory conversion the golden-file test above uses. The golden-file
+    /// fixtures alone never exercise this path, since `process_file` only
+    /// streams files above `--max-inmem`.
+    #[test]
+    fn streaming_matches_inmem_conversion_at_chunk_boundary() {
+        let mut content = vec![b'a'; STREAM_CHUNK_SIZE - 1];
+        content.push(b'\r');
+        content.push(b'\n');
+        content.extend_from_slice(b"tail\r\n");
+        let expected = convert_like_process_file(&content);
+
+        let dir = std::env::temp_dir().join(format!("lf-streaming-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let path = dir.join("boundary.txt");
+        fs::write(&path, &content).expect("write fixture");
+
+        process_file_streaming(&path).expect("streaming conversion");
+        let actual = fs::read(&path).expect("read converted file");
+
+        fs::remove_file(&path).ok();
+        fs::remove_dir(&dir).ok();
+
+        assert_eq!(actual, expected);
+    }
 }